@@ -1,40 +1,160 @@
+use std::collections::{HashSet, VecDeque};
 use std::env;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use futures::future::join_all;
+use futures_core::Stream;
+use tokio::sync::watch;
 use tokio::time;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::StreamExt;
 use tonic::{transport::Server, Request, Response, Status};
 use kube::{Client, Api, api::{Patch, PatchParams}};
 use k8s_openapi::api::core::v1::Pod;
 use serde_json::json;
 
+mod membership;
+mod metrics;
+mod neighbor_cache;
+mod rpc_helper;
+mod rules;
+
 pub mod cell {
     tonic::include_proto!("cell");
 }
 
 use cell::cell_service_server::{CellService, CellServiceServer};
 use cell::{Empty, Status as CellStatus};
-use cell::cell_service_client::CellServiceClient;
+use membership::Membership;
+use metrics::Metrics;
+use neighbor_cache::NeighborCache;
+use rpc_helper::RpcHelper;
+use rules::{Rule, Topology};
+
+/// How many past generations' `alive` values we keep around so a neighbor
+/// that already raced ahead to generation N can still answer a request for
+/// the N-1 snapshot it already overwrote locally.
+const HISTORY_LEN: usize = 8;
+
+/// How long the gather step will keep retrying a single neighbor for the
+/// exact generation it needs before giving up and treating it as dead.
+const GEN_WAIT_DEADLINE: Duration = Duration::from_millis(900);
+const GEN_RETRY_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Debug, Clone)]
 struct CellState {
     alive: bool,
     generation: i32,
+    /// Ring buffer of `(generation, alive)`, most recent last.
+    history: VecDeque<(i32, bool)>,
+}
+
+impl CellState {
+    fn record(&mut self) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((self.generation, self.alive));
+    }
 }
 
 #[derive(Debug)]
 struct MyCell {
     state: Arc<Mutex<CellState>>,
+    /// Broadcasts every committed `CellStatus` to whoever is subscribed;
+    /// fed by the game loop right after `generation`/`alive` change.
+    watch_tx: watch::Sender<CellStatus>,
 }
 
 #[tonic::async_trait]
 impl CellService for MyCell {
-    async fn get_status(&self, _request: Request<Empty>) -> Result<Response<CellStatus>, Status> {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<CellStatus, Status>> + Send + 'static>>;
+
+    async fn get_status(&self, request: Request<Empty>) -> Result<Response<CellStatus>, Status> {
+        let requested_generation = request.into_inner().generation;
         let state = self.state.lock().unwrap();
+
+        // A negative generation means "whatever you've got"; 0 is a real,
+        // requestable generation and must not be treated as that wildcard.
+        // Otherwise prefer an exact match, falling back to history for a
+        // generation we've already moved past.
+        if requested_generation >= 0 && requested_generation != state.generation {
+            if let Some((_, alive)) = state
+                .history
+                .iter()
+                .find(|(generation, _)| *generation == requested_generation)
+            {
+                return Ok(Response::new(CellStatus {
+                    alive: *alive,
+                    generation: requested_generation,
+                }));
+            }
+        }
+
         Ok(Response::new(CellStatus {
             alive: state.alive,
             generation: state.generation,
         }))
     }
+
+    async fn subscribe(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let stream = WatchStream::new(self.watch_tx.subscribe()).map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Wait for `neighbor_id`'s status at exactly `target_generation`. The
+/// pushed cache only ever holds the *latest* value the neighbor broadcast,
+/// so if our tick is running behind its Subscribe stream may have already
+/// moved past `target_generation` before we ever see it there. Each time
+/// the cache misses, fall back to a direct `get_status(generation =
+/// target_generation)` call so the neighbor's own generation-history ring
+/// buffer (see `get_status` above) can answer from the past instead of us
+/// just spinning until the deadline. Retries with a short fixed delay until
+/// `GEN_WAIT_DEADLINE` elapses; returns `None` if the generation never
+/// showed up in time (e.g. the neighbor is actually unreachable).
+async fn wait_for_neighbor_generation(
+    cache: &NeighborCache,
+    rpc: &RpcHelper,
+    neighbor_id: i32,
+    url: &str,
+    target_generation: i32,
+) -> Option<bool> {
+    let deadline = Instant::now() + GEN_WAIT_DEADLINE;
+
+    loop {
+        if let Some(status) = cache.get(neighbor_id).await {
+            if status.generation == target_generation {
+                return Some(status.alive);
+            }
+        }
+
+        let response = rpc
+            .call(neighbor_id, url, |mut client| async move {
+                client
+                    .get_status(tonic::Request::new(Empty {
+                        generation: target_generation,
+                    }))
+                    .await
+            })
+            .await;
+
+        if let Some(status) = response {
+            let status = status.into_inner();
+            if status.generation == target_generation {
+                return Some(status.alive);
+            }
+        }
+
+        if Instant::now() >= deadline {
+            return None;
+        }
+        time::sleep(GEN_RETRY_INTERVAL).await;
+    }
 }
 
 #[tokio::main]
@@ -48,29 +168,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let id: i32 = id_str.parse().unwrap_or(0);
     
     // Grid Configuration
-    let width_str = env::var("GRID_WIDTH").unwrap_or_else(|_| "10".to_string());
-    let width: i32 = width_str.parse().unwrap_or(10);
-    
+    let topology = Topology::from_env(10);
+    let width = topology.width;
+    let rule = Rule::from_env();
+
     let interval_ms_str = env::var("TICK_INTERVAL_MS").unwrap_or_else(|_| "1000".to_string());
     let interval_ms: u64 = interval_ms_str.parse().unwrap_or(1000);
 
     let x = id % width;
     let y = id / width;
 
-    println!("Identity: ID={}, X={}, Y={} (Grid: Width={}, Interval={}ms)", id, x, y, width, interval_ms);
+    println!(
+        "Identity: ID={}, X={}, Y={} (Grid: {}x{}, Wrap={}, Rule={:?}, Interval={}ms)",
+        id, x, y, topology.width, topology.height, topology.wrap, rule, interval_ms
+    );
 
     // Initial State: Random or based on ID?
     // Let's make even IDs alive for initial entropy
     let initial_alive = id % 2 == 0;
     
-    let state = Arc::new(Mutex::new(CellState {
+    let mut initial_state = CellState {
         alive: initial_alive,
         generation: 0,
-    }));
+        history: VecDeque::new(),
+    };
+    initial_state.record();
+    let (watch_tx, _watch_rx) = watch::channel(CellStatus {
+        alive: initial_state.alive,
+        generation: initial_state.generation,
+    });
+    let state = Arc::new(Mutex::new(initial_state));
 
     // 2. Start gRPC Server
     let addr = "0.0.0.0:50051".parse()?;
-    let cell_service = MyCell { state: state.clone() };
+    let cell_service = MyCell {
+        state: state.clone(),
+        watch_tx: watch_tx.clone(),
+    };
 
     println!("Starting gRPC server on {}", addr);
     
@@ -84,55 +218,135 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 3. Kubernetes Client
     // In-cluster config is assumed
-    let client = Client::try_default().await; 
+    let client = Client::try_default().await;
     let namespace = env::var("NAMESPACE").unwrap_or_else(|_| "cellular-automaton".to_string());
 
+    // Membership: live set of cell ids actually present in the cluster,
+    // from watching the `cell` Service's EndpointSlices.
+    let membership = Membership::new();
+    if let Ok(c) = &client {
+        membership.spawn_watcher(c.clone(), &namespace);
+    }
+
+    // Long-lived connection pool to neighbors, reused across ticks instead of
+    // reconnecting every time.
+    let rpc = RpcHelper::new();
+
+    // Push-based neighbor view: one Subscribe stream per neighbor, kept in a
+    // cache the game loop reads instead of polling every tick. The neighbor
+    // set can now change at runtime as membership changes, so subscribers
+    // are spawned lazily the first time a neighbor is seen (below) rather
+    // than once upfront.
+    let neighbor_cache = NeighborCache::new();
+    let subscribed_neighbors: Arc<Mutex<HashSet<i32>>> = Arc::new(Mutex::new(HashSet::new()));
+
+    // Metrics: Prometheus scrape endpoint on a second HTTP listener.
+    let metrics = Metrics::new(id);
+    metrics.clone().serve("0.0.0.0:9090".parse()?);
+
     // 4. Game Loop
     let mut interval = time::interval(Duration::from_millis(interval_ms));
-    
+
     loop {
         interval.tick().await;
+        metrics.ticks_processed.add(1, &[]);
 
         // SKIP gathering if client failed to init (e.g. running locally without k8s context)
         // But for production logic we assume it works.
-        
-        // Calculate neighbors
-        let neighbors = get_neighbors(id, width); 
-        let mut alive_neighbors = 0;
-
-        for neighbor_id in neighbors {
-            // Address: cell-{id}.cell.cellular-automaton.svc.cluster.local:50051
-            // Use short DNS: cell-{id}.cell
-            let url = format!("http://cell-{}.cell.{}.svc.cluster.local:50051", neighbor_id, namespace);
-            
-            // Connect with timeout
-            if let Ok(mut client) = CellServiceClient::connect(url.clone()).await {
-                 let request = tonic::Request::new(Empty {});
-                 if let Ok(response) = client.get_status(request).await {
-                     if response.into_inner().alive {
-                         alive_neighbors += 1;
-                     }
-                 }
+
+        // Calculate neighbors from the live membership view; the grid itself
+        // is sized from the observed population once membership has synced.
+        let effective_topology = effective_topology(&membership, topology);
+        let neighbors = get_neighbors(id, &effective_topology, &membership);
+        let mut alive_neighbors: u8 = 0;
+
+        // Make sure every currently-live neighbor has a subscriber; new ones
+        // (e.g. after a scale-up) are picked up here as membership grows.
+        {
+            let mut subscribed = subscribed_neighbors.lock().unwrap();
+            for &neighbor_id in &neighbors {
+                if subscribed.insert(neighbor_id) {
+                    neighbor_cache.spawn_subscriber(
+                        rpc.clone(),
+                        neighbor_id,
+                        neighbor_url(neighbor_id, &namespace),
+                    );
+                }
+            }
+        }
+
+        // We're about to compute generation `target_generation + 1`, so every
+        // neighbor must be asked for its committed snapshot of exactly
+        // `target_generation` (the barrier).
+        let target_generation = state.lock().unwrap().generation;
+
+        // Fan out to every neighbor concurrently so one slow/dead neighbor
+        // only costs up to GEN_WAIT_DEADLINE once, not once per neighbor.
+        let gather_start = Instant::now();
+        let results = join_all(neighbors.into_iter().map(|neighbor_id| {
+            let neighbor_cache = &neighbor_cache;
+            let rpc = &rpc;
+            let url = neighbor_url(neighbor_id, &namespace);
+            async move {
+                let rpc_start = Instant::now();
+                let result = wait_for_neighbor_generation(
+                    neighbor_cache,
+                    rpc,
+                    neighbor_id,
+                    &url,
+                    target_generation,
+                )
+                .await;
+                (rpc_start.elapsed(), result)
+            }
+        }))
+        .await;
+
+        for (rpc_elapsed, result) in results {
+            metrics
+                .neighbor_rpc_duration
+                .record(rpc_elapsed.as_secs_f64(), &[]);
+
+            match result {
+                Some(alive) => {
+                    if alive {
+                        alive_neighbors += 1;
+                    }
+                }
+                // The neighbor never reported target_generation in time; count it as dead/0.
+                None => metrics.neighbor_rpc_failures.add(1, &[]),
             }
-            // If unreachable, count as dead/0
         }
+        metrics
+            .gather_duration
+            .record(gather_start.elapsed().as_secs_f64(), &[]);
 
         // Apply Rules
         let mut s = state.lock().unwrap();
         let was_alive = s.alive;
-        
-        let next_alive = if was_alive {
-            alive_neighbors == 2 || alive_neighbors == 3
-        } else {
-            alive_neighbors == 3
-        };
+
+        let next_alive = rule.next_alive(was_alive, alive_neighbors);
 
         s.alive = next_alive;
         s.generation += 1;
+        s.record();
         let gen = s.generation;
         let is_alive = s.alive;
         drop(s); // release lock
 
+        if is_alive && !was_alive {
+            metrics.births.add(1, &[]);
+        } else if was_alive && !is_alive {
+            metrics.deaths.add(1, &[]);
+        }
+        metrics.record_state(is_alive, gen);
+
+        // Push the new state to subscribers (our own neighbors' caches).
+        let _ = watch_tx.send(CellStatus {
+            alive: is_alive,
+            generation: gen,
+        });
+
         println!("Tick {}: Alive={}, Neighbors={} -> Next={}", gen, was_alive, alive_neighbors, is_alive);
 
         // Update K8s Label
@@ -151,23 +365,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-fn get_neighbors(id: i32, width: i32) -> Vec<i32> {
-    let size = width * width; // Assuming 10x10 = 100 total
+/// DNS address of a neighbor's gRPC endpoint: `cell-{id}.cell.{namespace}.svc.cluster.local:50051`.
+fn neighbor_url(neighbor_id: i32, namespace: &str) -> String {
+    format!(
+        "http://cell-{}.cell.{}.svc.cluster.local:50051",
+        neighbor_id, namespace
+    )
+}
+
+/// Geometric neighbors of `id` on `topology`'s grid, intersected with the
+/// live membership set so a scaled-down or not-yet-joined neighbor doesn't
+/// count as a spurious death. When `topology.wrap` is set the grid is
+/// toroidal and edge cells wrap around to the opposite side. If membership
+/// is unknown (no Kubernetes client, or the watch hasn't synced yet) we
+/// can't tell live neighbors from absent ones, so fall back to pure
+/// geometry rather than reporting zero neighbors for every cell.
+fn get_neighbors(id: i32, topology: &Topology, membership: &Membership) -> Vec<i32> {
+    let Topology { width, height, wrap } = *topology;
+    let size = topology.size();
     let x = id % width;
     let y = id / width;
-    
+    let trust_geometry_only = membership.is_unknown();
+
     let mut neighbors = Vec::new();
 
     for dy in -1..=1 {
         for dx in -1..=1 {
             if dx == 0 && dy == 0 { continue; }
-            
+
+            if wrap {
+                let nx = (x + dx + width) % width;
+                let ny = (y + dy + height) % height;
+                let nid = ny * width + nx;
+                if trust_geometry_only || membership.contains(nid) {
+                    neighbors.push(nid);
+                }
+                continue;
+            }
+
             let nx = x + dx;
             let ny = y + dy;
 
-            if nx >= 0 && nx < width && ny >= 0 && ny < width { // No wrapping for now
+            if nx >= 0 && nx < width && ny >= 0 && ny < height {
                 let nid = ny * width + nx;
-                if nid >= 0 && nid < size {
+                if nid >= 0 && nid < size && (trust_geometry_only || membership.contains(nid)) {
                     neighbors.push(nid);
                 }
             }
@@ -175,3 +416,244 @@ fn get_neighbors(id: i32, width: i32) -> Vec<i32> {
     }
     neighbors
 }
+
+/// Topology to use for this tick. Once the membership watch has observed
+/// more than just this cell, a square grid's side is sized from the
+/// *highest observed cell id* rather than the raw population count:
+/// population isn't meaningful when ids aren't densely packed (a NotReady
+/// pod leaves a gap without shrinking the addressable space, which would
+/// otherwise put higher ids out of bounds), and deriving size from
+/// population reshuffles every live cell's coordinates whenever the count
+/// crosses a perfect-square boundary instead of just the neighbor(s) that
+/// actually changed. An explicitly configured non-square grid (`width !=
+/// height`) is assumed intentional and left alone. Seeing only ourselves
+/// (population 1) isn't enough to size off of either — early in a
+/// staggered rollout that would shrink the grid to 1x1 and isolate every
+/// live cell before the rest of the StatefulSet has a chance to join.
+fn effective_topology(membership: &Membership, configured: Topology) -> Topology {
+    if configured.width != configured.height {
+        return configured;
+    }
+    if membership.population() <= 1 {
+        return configured;
+    }
+    let Some(max_id) = membership.max_id() else {
+        return configured;
+    };
+    let side = ((max_id + 1) as f64).sqrt().ceil() as i32;
+    Topology {
+        width: side,
+        height: side,
+        ..configured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounded(width: i32, height: i32) -> Topology {
+        Topology {
+            width,
+            height,
+            wrap: false,
+        }
+    }
+
+    fn wrapped(width: i32, height: i32) -> Topology {
+        Topology {
+            width,
+            height,
+            wrap: true,
+        }
+    }
+
+    #[test]
+    fn get_neighbors_bounded_center_has_eight() {
+        let topology = bounded(5, 5);
+        let membership = Membership::from_ids(0..topology.size());
+        assert_eq!(get_neighbors(12, &topology, &membership).len(), 8);
+    }
+
+    #[test]
+    fn get_neighbors_bounded_corner_has_three_no_wrap() {
+        let topology = bounded(5, 5);
+        let membership = Membership::from_ids(0..topology.size());
+        assert_eq!(get_neighbors(0, &topology, &membership).len(), 3);
+    }
+
+    #[test]
+    fn get_neighbors_wrapped_corner_has_eight() {
+        let topology = wrapped(5, 5);
+        let membership = Membership::from_ids(0..topology.size());
+        let neighbors = get_neighbors(0, &topology, &membership);
+        assert_eq!(neighbors.len(), 8);
+        // Wrapping the top-left corner should reach the opposite edges.
+        assert!(neighbors.contains(&4)); // left wrap on the same row
+        assert!(neighbors.contains(&20)); // top wrap on the same column
+        assert!(neighbors.contains(&24)); // diagonal wrap to the far corner
+    }
+
+    #[test]
+    fn get_neighbors_excludes_ids_absent_from_membership() {
+        let topology = bounded(5, 5);
+        // Center cell 12's 8 geometric neighbors are {6,7,8,11,13,16,17,18};
+        // only these four are actually live.
+        let membership = Membership::from_ids([7, 11, 13, 17]);
+        assert_eq!(get_neighbors(12, &topology, &membership).len(), 4);
+    }
+
+    #[test]
+    fn get_neighbors_falls_back_to_geometry_when_membership_unknown() {
+        let topology = bounded(5, 5);
+        let membership = Membership::new();
+        assert_eq!(get_neighbors(12, &topology, &membership).len(), 8);
+    }
+
+    #[test]
+    fn effective_topology_keeps_configured_size_until_membership_known() {
+        let configured = bounded(10, 10);
+        let membership = Membership::new();
+        let result = effective_topology(&membership, configured);
+        assert_eq!((result.width, result.height), (10, 10));
+    }
+
+    #[test]
+    fn effective_topology_sizes_from_highest_id_not_population() {
+        let configured = bounded(10, 10);
+        // Population is 4, but id 4 (a NotReady gap at id 2) needs side 3.
+        let membership = Membership::from_ids([0, 1, 3, 4]);
+        let result = effective_topology(&membership, configured);
+        assert_eq!((result.width, result.height), (3, 3));
+        // The highest id must fit inside the derived grid.
+        assert!(4 < result.size());
+    }
+
+    #[test]
+    fn effective_topology_keeps_configured_size_when_only_self_observed() {
+        let configured = bounded(10, 10);
+        // Staggered rollout: the watch has only caught up with this cell so
+        // far. Sizing off id 7 alone would shrink the grid to 3x3 and likely
+        // isolate (and kill) this very cell.
+        let membership = Membership::from_ids([7]);
+        let result = effective_topology(&membership, configured);
+        assert_eq!((result.width, result.height), (10, 10));
+    }
+
+    #[test]
+    fn effective_topology_leaves_non_square_grid_alone() {
+        let configured = bounded(10, 5);
+        let membership = Membership::from_ids(0..50);
+        let result = effective_topology(&membership, configured);
+        assert_eq!((result.width, result.height), (10, 5));
+    }
+
+    #[tokio::test]
+    async fn get_status_recalls_history_after_generation_advances() {
+        let mut initial_state = CellState {
+            alive: true,
+            generation: 0,
+            history: VecDeque::new(),
+        };
+        initial_state.record();
+        let state = Arc::new(Mutex::new(initial_state));
+        let (watch_tx, _rx) = watch::channel(CellStatus {
+            alive: true,
+            generation: 0,
+        });
+        let cell = MyCell {
+            state: state.clone(),
+            watch_tx,
+        };
+
+        // Advance past generation 0, as the game loop would.
+        {
+            let mut s = state.lock().unwrap();
+            s.alive = false;
+            s.generation = 1;
+            s.record();
+        }
+
+        let response = cell
+            .get_status(Request::new(Empty { generation: 0 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.generation, 0);
+        assert!(response.alive);
+    }
+
+    #[tokio::test]
+    async fn get_status_generation_zero_is_not_a_wildcard() {
+        // A freshly started or just-rejoined neighbor is still at generation
+        // 0 and dead; a barrier request for exactly generation 0 must see
+        // that, not get treated as "whatever you've got" and handed a later
+        // generation's state.
+        let mut initial_state = CellState {
+            alive: false,
+            generation: 0,
+            history: VecDeque::new(),
+        };
+        initial_state.record();
+        let state = Arc::new(Mutex::new(initial_state));
+        let (watch_tx, _rx) = watch::channel(CellStatus {
+            alive: false,
+            generation: 0,
+        });
+        let cell = MyCell {
+            state: state.clone(),
+            watch_tx,
+        };
+
+        {
+            let mut s = state.lock().unwrap();
+            s.alive = true;
+            s.generation = 1;
+            s.record();
+        }
+
+        let response = cell
+            .get_status(Request::new(Empty { generation: 0 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.generation, 0);
+        assert!(!response.alive);
+    }
+
+    #[tokio::test]
+    async fn get_status_evicted_generation_falls_back_to_current() {
+        let mut initial_state = CellState {
+            alive: false,
+            generation: 0,
+            history: VecDeque::new(),
+        };
+        initial_state.record();
+        let state = Arc::new(Mutex::new(initial_state));
+        let (watch_tx, _rx) = watch::channel(CellStatus {
+            alive: false,
+            generation: 0,
+        });
+        let cell = MyCell {
+            state: state.clone(),
+            watch_tx,
+        };
+
+        // Push the history ring buffer past its capacity.
+        for gen in 1..=(HISTORY_LEN as i32 + 5) {
+            let mut s = state.lock().unwrap();
+            s.alive = !s.alive;
+            s.generation = gen;
+            s.record();
+        }
+
+        // Generation 0 has long since been evicted; we should get current state back.
+        let response = cell
+            .get_status(Request::new(Empty { generation: 0 }))
+            .await
+            .unwrap()
+            .into_inner();
+        let current_generation = state.lock().unwrap().generation;
+        assert_eq!(response.generation, current_generation);
+    }
+}
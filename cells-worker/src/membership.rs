@@ -0,0 +1,122 @@
+//! Kubernetes-native membership tracking: the live set of cell ids is kept
+//! current by watching the `cell` headless Service's `EndpointSlice`s.
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use futures::StreamExt;
+use k8s_openapi::api::discovery::v1::EndpointSlice;
+use kube::runtime::{watcher, watcher::Event};
+use kube::{Api, Client};
+
+/// Live set of cell ids currently present (and Ready) in the `cell`
+/// Service's endpoints, kept current by a background watch task. `id` is
+/// parsed back out of each endpoint's pod hostname (`cell-{id}`).
+#[derive(Clone)]
+pub struct Membership {
+    present: Arc<RwLock<HashSet<i32>>>,
+}
+
+impl Membership {
+    pub fn new() -> Self {
+        Membership {
+            present: Arc::new(RwLock::new(HashSet::new())),
+        }
+    }
+
+    pub fn contains(&self, id: i32) -> bool {
+        self.present.read().unwrap().contains(&id)
+    }
+
+    /// True if we haven't observed any peer yet, either because the watch
+    /// hasn't completed its first list or because there's no Kubernetes
+    /// client at all (e.g. running locally). Callers should treat this as
+    /// "membership is unknown", not "membership is empty".
+    pub fn is_unknown(&self) -> bool {
+        self.present.read().unwrap().is_empty()
+    }
+
+    /// Highest cell id currently observed, or `None` before the watch has
+    /// seen anything. Used to size the grid instead of the raw population
+    /// count, which isn't meaningful when ids aren't densely packed (a
+    /// NotReady pod leaves a gap without shrinking the addressable space).
+    pub fn max_id(&self) -> Option<i32> {
+        self.present.read().unwrap().iter().copied().max()
+    }
+
+    /// Number of cell ids currently observed. Unlike `max_id`, this isn't
+    /// used to size the grid (it isn't dense-id-aware) — only to tell a
+    /// cluster that has barely started joining from one that's actually
+    /// settled on a small size.
+    pub fn population(&self) -> usize {
+        self.present.read().unwrap().len()
+    }
+
+    /// Watch the `cell` Service's `EndpointSlice`s in `namespace` and keep
+    /// the live set updated as pods join, leave, or flip Ready state.
+    pub fn spawn_watcher(&self, client: Client, namespace: &str) {
+        let present = self.present.clone();
+        let api: Api<EndpointSlice> = Api::namespaced(client, namespace);
+        let config = watcher::Config::default().labels("kubernetes.io/service-name=cell");
+
+        tokio::spawn(async move {
+            let mut stream = watcher(api, config).boxed();
+            while let Some(event) = stream.next().await {
+                match event {
+                    Ok(Event::Applied(slice)) => apply_slice(&present, &slice),
+                    Ok(Event::Deleted(slice)) => remove_slice(&present, &slice),
+                    Ok(Event::Restarted(slices)) => {
+                        present.write().unwrap().clear();
+                        for slice in &slices {
+                            apply_slice(&present, slice);
+                        }
+                    }
+                    Err(err) => eprintln!("membership watch error: {}", err),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+impl Membership {
+    /// Build a `Membership` pre-populated with `ids`, for tests that need a
+    /// known live set without spinning up a watcher.
+    pub fn from_ids(ids: impl IntoIterator<Item = i32>) -> Self {
+        Membership {
+            present: Arc::new(RwLock::new(ids.into_iter().collect())),
+        }
+    }
+}
+
+fn cell_id_from_hostname(hostname: &str) -> Option<i32> {
+    hostname.strip_prefix("cell-").and_then(|s| s.parse().ok())
+}
+
+fn apply_slice(present: &Arc<RwLock<HashSet<i32>>>, slice: &EndpointSlice) {
+    let mut guard = present.write().unwrap();
+    for endpoint in &slice.endpoints {
+        let ready = endpoint
+            .conditions
+            .as_ref()
+            .and_then(|c| c.ready)
+            .unwrap_or(true);
+        let Some(id) = endpoint.hostname.as_deref().and_then(cell_id_from_hostname) else {
+            continue;
+        };
+        if ready {
+            guard.insert(id);
+        } else {
+            guard.remove(&id);
+        }
+    }
+}
+
+fn remove_slice(present: &Arc<RwLock<HashSet<i32>>>, slice: &EndpointSlice) {
+    let mut guard = present.write().unwrap();
+    for endpoint in &slice.endpoints {
+        if let Some(id) = endpoint.hostname.as_deref().and_then(cell_id_from_hostname) {
+            guard.remove(&id);
+        }
+    }
+}
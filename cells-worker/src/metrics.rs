@@ -0,0 +1,137 @@
+//! Metrics subsystem for the game loop: instruments are reported via the
+//! `opentelemetry` API and exposed as a Prometheus scrape endpoint instead
+//! of `println!` logging.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_prometheus::PrometheusExporter;
+use prometheus::{Encoder, TextEncoder};
+
+/// `alive`/`generation` only change once per tick but the OTel API exposes
+/// gauges as callback-driven, so we stash the latest values here and read
+/// them back when the exporter is scraped.
+struct GaugeState {
+    alive: AtomicU64,
+    generation: AtomicI64,
+}
+
+/// All the instruments the game loop reports into, plus the exporter used
+/// to render them in Prometheus exposition format.
+#[derive(Clone)]
+pub struct Metrics {
+    pub ticks_processed: Counter<u64>,
+    pub births: Counter<u64>,
+    pub deaths: Counter<u64>,
+    pub gather_duration: Histogram<f64>,
+    pub neighbor_rpc_duration: Histogram<f64>,
+    pub neighbor_rpc_failures: Counter<u64>,
+    state: Arc<GaugeState>,
+    exporter: PrometheusExporter,
+}
+
+impl Metrics {
+    pub fn new(cell_id: i32) -> Self {
+        let exporter = opentelemetry_prometheus::exporter().init();
+        let meter: Meter = global::meter("cells-worker");
+        let labels = [KeyValue::new("cell_id", cell_id.to_string())];
+
+        let state = Arc::new(GaugeState {
+            alive: AtomicU64::new(0),
+            generation: AtomicI64::new(0),
+        });
+
+        let gauge_state = state.clone();
+        let gauge_labels = labels.clone();
+        meter
+            .u64_observable_gauge("cell_alive")
+            .with_description("1 if the cell is currently alive, 0 otherwise")
+            .with_callback(move |observer| {
+                observer.observe(gauge_state.alive.load(Ordering::Relaxed), &gauge_labels)
+            })
+            .init();
+
+        let gauge_state = state.clone();
+        let gauge_labels = labels.clone();
+        meter
+            .i64_observable_gauge("cell_generation")
+            .with_description("Current generation number")
+            .with_callback(move |observer| {
+                observer.observe(gauge_state.generation.load(Ordering::Relaxed), &gauge_labels)
+            })
+            .init();
+
+        Metrics {
+            ticks_processed: meter
+                .u64_counter("cell_ticks_processed_total")
+                .with_description("Number of game-loop ticks processed")
+                .init(),
+            births: meter
+                .u64_counter("cell_births_total")
+                .with_description("Number of dead-to-alive transitions")
+                .init(),
+            deaths: meter
+                .u64_counter("cell_deaths_total")
+                .with_description("Number of alive-to-dead transitions")
+                .init(),
+            gather_duration: meter
+                .f64_histogram("cell_gather_duration_seconds")
+                .with_description("Time spent gathering all neighbor statuses for one tick")
+                .init(),
+            neighbor_rpc_duration: meter
+                .f64_histogram("cell_neighbor_rpc_duration_seconds")
+                .with_description("Latency of a single neighbor status fetch")
+                .init(),
+            neighbor_rpc_failures: meter
+                .u64_counter("cell_neighbor_rpc_failures_total")
+                .with_description("Neighbor fetches that never produced the requested generation")
+                .init(),
+            state,
+            exporter,
+        }
+    }
+
+    /// Update the gauge snapshot read back on the next scrape.
+    pub fn record_state(&self, alive: bool, generation: i32) {
+        self.state.alive.store(alive as u64, Ordering::Relaxed);
+        self.state
+            .generation
+            .store(generation as i64, Ordering::Relaxed);
+    }
+
+    /// Serve `/metrics` in Prometheus exposition format on `addr`, alongside
+    /// the gRPC server started in `main`.
+    pub fn serve(self, addr: SocketAddr) {
+        tokio::spawn(async move {
+            let metrics = self;
+            let make_svc = make_service_fn(move |_conn| {
+                let metrics = metrics.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |_req: Request<Body>| {
+                        let metrics = metrics.clone();
+                        async move { Ok::<_, Infallible>(metrics.render()) }
+                    }))
+                }
+            });
+
+            println!("Starting metrics server on {}", addr);
+            if let Err(err) = Server::bind(&addr).serve(make_svc).await {
+                eprintln!("metrics server error: {}", err);
+            }
+        });
+    }
+
+    fn render(&self) -> Response<Body> {
+        let encoder = TextEncoder::new();
+        let metric_families = self.exporter.registry().gather();
+        let mut buffer = Vec::new();
+        encoder.encode(&metric_families, &mut buffer).unwrap();
+        Response::new(Body::from(buffer))
+    }
+}
@@ -0,0 +1,69 @@
+//! Event-driven counterpart to polling: one long-lived `Subscribe` stream
+//! per neighbor, feeding a local cache that the game loop reads at tick
+//! time instead of issuing a fresh RPC per neighbor per tick.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use crate::cell::{Empty, Status as CellStatus};
+use crate::rpc_helper::RpcHelper;
+
+/// How long to wait before resubscribing after a stream ends or fails to
+/// open (the latter is also governed by `RpcHelper`'s own per-peer backoff).
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Live view of neighbor statuses, kept up to date by background
+/// subscription tasks.
+#[derive(Clone)]
+pub struct NeighborCache {
+    entries: Arc<Mutex<HashMap<i32, CellStatus>>>,
+}
+
+impl NeighborCache {
+    pub fn new() -> Self {
+        NeighborCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn get(&self, neighbor_id: i32) -> Option<CellStatus> {
+        self.entries.lock().await.get(&neighbor_id).cloned()
+    }
+
+    async fn set(&self, neighbor_id: i32, status: CellStatus) {
+        self.entries.lock().await.insert(neighbor_id, status);
+    }
+
+    /// Spawn a task that subscribes to `neighbor_id` at `url` and keeps
+    /// resubscribing for as long as the process runs, forwarding every
+    /// pushed `Status` into the cache.
+    pub fn spawn_subscriber(&self, rpc: RpcHelper, neighbor_id: i32, url: String) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            loop {
+                // Subscribe ignores the requested generation and just pushes
+                // every commit, but -1 documents that we're not pinning to one.
+                let stream = rpc
+                    .call(neighbor_id, &url, |mut client| async move {
+                        client
+                            .subscribe(tonic::Request::new(Empty { generation: -1 }))
+                            .await
+                    })
+                    .await;
+
+                if let Some(response) = stream {
+                    let mut inner = response.into_inner();
+                    while let Ok(Some(status)) = inner.message().await {
+                        cache.set(neighbor_id, status).await;
+                    }
+                }
+                // Stream ended, errored, or the connection is backing off;
+                // wait a beat and try to resubscribe.
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+    }
+}
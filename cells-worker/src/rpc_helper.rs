@@ -0,0 +1,128 @@
+//! Connection pooling and retry/backoff for outbound neighbor RPCs. Instead
+//! of dialing a fresh connection for every call, we keep one long-lived
+//! `CellServiceClient` per neighbor and only pay the handshake cost again
+//! after an error. A small per-peer backoff state keeps a flaky neighbor
+//! from being redialed on every single tick.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::cell::cell_service_client::CellServiceClient;
+
+/// Base delay for the first retry after a peer starts failing.
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+/// Upper bound so a long-dead peer is still retried occasionally.
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// Timeout for establishing the connection to a neighbor; a dial that takes
+/// longer counts as a failure for the peer's backoff.
+const CONNECT_DEADLINE: Duration = Duration::from_millis(800);
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Backoff bookkeeping for a single neighbor.
+struct PeerState {
+    client: Option<CellServiceClient<Channel>>,
+    failures: u32,
+    retry_after: Instant,
+}
+
+impl PeerState {
+    fn fresh() -> Self {
+        PeerState {
+            client: None,
+            failures: 0,
+            retry_after: Instant::now(),
+        }
+    }
+
+    fn note_success(&mut self) {
+        self.failures = 0;
+        self.retry_after = Instant::now();
+    }
+
+    fn note_failure(&mut self) {
+        self.client = None;
+        self.failures = self.failures.saturating_add(1);
+        let delay = BACKOFF_BASE
+            .saturating_mul(1 << (self.failures - 1).min(8))
+            .min(BACKOFF_MAX);
+        self.retry_after = Instant::now() + delay;
+    }
+}
+
+/// Pool of persistent gRPC connections to neighbor cells, keyed by neighbor
+/// id. Cheap to clone; the actual state lives behind an `Arc`. Each peer
+/// gets its own `Mutex` so a slow/unreachable neighbor only blocks calls to
+/// that neighbor, not the whole pool — the outer map lock is only ever held
+/// long enough to look up or insert that per-peer handle.
+#[derive(Clone)]
+pub struct RpcHelper {
+    peers: Arc<Mutex<HashMap<i32, Arc<Mutex<PeerState>>>>>,
+}
+
+impl RpcHelper {
+    pub fn new() -> Self {
+        RpcHelper {
+            peers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn peer(&self, neighbor_id: i32) -> Arc<Mutex<PeerState>> {
+        let mut peers = self.peers.lock().await;
+        peers
+            .entry(neighbor_id)
+            .or_insert_with(|| Arc::new(Mutex::new(PeerState::fresh())))
+            .clone()
+    }
+
+    /// Fetch (reconnecting if needed) the client for `neighbor_id`, run
+    /// `call` against it, and update the peer's backoff state based on the
+    /// outcome. Returns `None` if the peer is still in its backoff window or
+    /// the connection/call failed.
+    pub async fn call<F, Fut, T>(&self, neighbor_id: i32, url: &str, call: F) -> Option<T>
+    where
+        F: FnOnce(CellServiceClient<Channel>) -> Fut,
+        Fut: std::future::Future<Output = Result<T, tonic::Status>>,
+    {
+        let peer = self.peer(neighbor_id).await;
+        let mut state = peer.lock().await;
+
+        if Instant::now() < state.retry_after {
+            return None;
+        }
+
+        let mut client = match state.client.take() {
+            Some(client) => client,
+            None => match Self::connect(url).await {
+                Ok(client) => client,
+                Err(_) => {
+                    state.note_failure();
+                    return None;
+                }
+            },
+        };
+
+        match call(client.clone()).await {
+            Ok(value) => {
+                state.client = Some(client);
+                state.note_success();
+                Some(value)
+            }
+            Err(_) => {
+                state.note_failure();
+                None
+            }
+        }
+    }
+
+    async fn connect(url: &str) -> Result<CellServiceClient<Channel>, tonic::transport::Error> {
+        let endpoint = Endpoint::from_shared(url.to_string())?
+            .connect_timeout(CONNECT_DEADLINE)
+            .timeout(REQUEST_TIMEOUT);
+        let channel = endpoint.connect().await?;
+        Ok(CellServiceClient::new(channel))
+    }
+}
@@ -0,0 +1,145 @@
+//! Configurable life-like rule sets (B/S notation) and grid topology,
+//! replacing the hardcoded Conway B3/S23 bounded grid with a general
+//! distributed cellular-automaton engine. Conway remains the default.
+
+use std::collections::HashSet;
+use std::env;
+
+/// A life-like rule in B(irth)/S(urvival) notation, e.g. `B3/S23` for
+/// Conway's Game of Life, `B36/S23` for HighLife, or `B2/S` for Seeds.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    birth: HashSet<u8>,
+    survival: HashSet<u8>,
+}
+
+impl Rule {
+    pub fn conway() -> Self {
+        Rule {
+            birth: HashSet::from([3]),
+            survival: HashSet::from([2, 3]),
+        }
+    }
+
+    /// Parse `B<digits>/S<digits>`. Unrecognized or empty input falls back
+    /// to Conway's rule rather than producing a rule with no transitions.
+    pub fn parse(spec: &str) -> Self {
+        let mut birth = HashSet::new();
+        let mut survival = HashSet::new();
+
+        for part in spec.split('/') {
+            let part = part.trim();
+            if let Some(digits) = part.strip_prefix('B').or_else(|| part.strip_prefix('b')) {
+                birth.extend(digits.chars().filter_map(|c| c.to_digit(10)).map(|d| d as u8));
+            } else if let Some(digits) = part.strip_prefix('S').or_else(|| part.strip_prefix('s')) {
+                survival.extend(digits.chars().filter_map(|c| c.to_digit(10)).map(|d| d as u8));
+            }
+        }
+
+        if birth.is_empty() && survival.is_empty() {
+            return Rule::conway();
+        }
+        Rule { birth, survival }
+    }
+
+    pub fn from_env() -> Self {
+        match env::var("GRID_RULE") {
+            Ok(spec) => Rule::parse(&spec),
+            Err(_) => Rule::conway(),
+        }
+    }
+
+    /// Whether a cell should be alive next generation given its current
+    /// state and its live neighbor count.
+    pub fn next_alive(&self, was_alive: bool, alive_neighbors: u8) -> bool {
+        if was_alive {
+            self.survival.contains(&alive_neighbors)
+        } else {
+            self.birth.contains(&alive_neighbors)
+        }
+    }
+}
+
+/// Grid shape and wraparound behavior.
+#[derive(Debug, Clone, Copy)]
+pub struct Topology {
+    pub width: i32,
+    pub height: i32,
+    pub wrap: bool,
+}
+
+impl Topology {
+    /// Reads `GRID_WIDTH`/`GRID_HEIGHT` (height defaults to width, keeping
+    /// the historical square grid) and `GRID_WRAP` (toroidal when truthy).
+    pub fn from_env(default_width: i32) -> Self {
+        let width = env::var("GRID_WIDTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_width);
+        let height = env::var("GRID_HEIGHT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(width);
+        let wrap = env::var("GRID_WRAP")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Topology {
+            width,
+            height,
+            wrap,
+        }
+    }
+
+    pub fn size(&self) -> i32 {
+        self.width * self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_conway() {
+        let rule = Rule::parse("B3/S23");
+        assert!(rule.next_alive(false, 3));
+        assert!(!rule.next_alive(false, 2));
+        assert!(rule.next_alive(true, 2));
+        assert!(rule.next_alive(true, 3));
+        assert!(!rule.next_alive(true, 4));
+    }
+
+    #[test]
+    fn parses_highlife() {
+        let rule = Rule::parse("B36/S23");
+        assert!(rule.next_alive(false, 3));
+        assert!(rule.next_alive(false, 6));
+        assert!(!rule.next_alive(false, 5));
+    }
+
+    #[test]
+    fn parses_seeds_with_empty_survival() {
+        let rule = Rule::parse("B2/S");
+        assert!(rule.next_alive(false, 2));
+        assert!(!rule.next_alive(false, 3));
+        // Nothing survives in Seeds, no matter the neighbor count.
+        assert!(!rule.next_alive(true, 2));
+        assert!(!rule.next_alive(true, 3));
+    }
+
+    #[test]
+    fn malformed_spec_falls_back_to_conway() {
+        let rule = Rule::parse("not a rule");
+        assert!(rule.next_alive(false, 3));
+        assert!(rule.next_alive(true, 2));
+        assert!(!rule.next_alive(true, 4));
+    }
+
+    #[test]
+    fn empty_spec_falls_back_to_conway() {
+        let rule = Rule::parse("");
+        assert!(rule.next_alive(false, 3));
+        assert!(rule.next_alive(true, 3));
+    }
+}